@@ -0,0 +1,368 @@
+//! Structured, typed argument and flag parsing for commands.
+//!
+//! Modeled on xflags: a command declares its positional arguments and flags
+//! up front (see [`ArgSpec`]), and the framework parses whatever is left of
+//! the message after the command name into a [`ParsedArgs`] before the
+//! handler runs, instead of every command hand-rolling its own `&str`
+//! splitting.
+
+use std::collections::HashMap;
+
+/// How many times an argument or flag may appear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// May be omitted.
+    Optional,
+    /// Must be present exactly once.
+    Required,
+    /// May be given any number of times. For positionals, only valid on the
+    /// last entry in [`ArgSpec::positionals`], where it collects everything
+    /// left over.
+    Repeated,
+}
+
+/// Declares a single positional argument.
+#[derive(Debug, Clone, Copy)]
+pub struct PositionalSpec {
+    pub name: &'static str,
+    pub arity: Arity,
+}
+
+/// Declares a single flag, e.g. `--channel` / `-c`.
+#[derive(Debug, Clone, Copy)]
+pub struct FlagSpec {
+    pub long: &'static str,
+    pub short: Option<char>,
+    pub takes_value: bool,
+    pub arity: Arity,
+}
+
+/// A command's full argument declaration, attached via
+/// `CommandOptions::args`.
+#[derive(Debug, Clone, Copy)]
+pub struct ArgSpec {
+    pub positionals: &'static [PositionalSpec],
+    pub flags: &'static [FlagSpec],
+}
+
+/// Why parsing `args` against an [`ArgSpec`] failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgError {
+    /// A `Required` positional or flag never showed up, or a flag whose
+    /// `FlagSpec::takes_value` is `true` was given without a value.
+    MissingRequired(&'static str),
+    /// A token looked like a flag but isn't declared in the spec.
+    UnknownFlag(String),
+    /// An `Optional` or `Required` flag (anything but `Repeated`) was given
+    /// more than once.
+    TooManyOccurrences(&'static str),
+    /// `--flag=value` was given for a flag whose `FlagSpec::takes_value` is
+    /// `false`.
+    UnexpectedValue(&'static str),
+}
+
+/// The positionals and flags left over after a command's name (and, if it
+/// matched via regex, its capture groups) have been consumed.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedArgs {
+    flags: HashMap<&'static str, Vec<String>>,
+    positionals: Vec<String>,
+}
+
+impl ParsedArgs {
+    /// All values a flag was given, in the order they appeared. `None` if
+    /// the flag wasn't present at all.
+    pub fn flag(&self, name: &str) -> Option<&[String]> {
+        self.flags.get(name).map(Vec::as_slice)
+    }
+
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positionals.get(index).map(String::as_str)
+    }
+
+    pub fn positionals(&self) -> &[String] {
+        &self.positionals
+    }
+}
+
+/// Parses `input` against `spec`, honouring the same double-quote grouping
+/// the rest of the framework gives users (`"two words"` is one token).
+///
+/// A token starting with `--` is a long flag (`--name=value` or
+/// `--name value`); a token starting with a single `-` is one or more short
+/// flags bundled together; a bare `--` stops flag parsing so everything
+/// after it is treated as positional, even if it looks like a flag.
+pub fn parse_args(input: &str, spec: &ArgSpec) -> Result<ParsedArgs, ArgError> {
+    let mut flags: HashMap<&'static str, Vec<String>> = HashMap::new();
+    let mut raw_positionals = Vec::new();
+    let mut positionals_only = false;
+
+    let mut tokens = tokenize(input).into_iter();
+
+    while let Some(token) = tokens.next() {
+        if positionals_only {
+            raw_positionals.push(token);
+            continue;
+        }
+
+        if token == "--" {
+            positionals_only = true;
+        } else if let Some(body) = token.strip_prefix("--") {
+            let (name, inline_value) = match body.find('=') {
+                Some(i) => (&body[..i], Some(body[i + 1..].to_owned())),
+                None => (body, None),
+            };
+
+            let flag = find_long(spec, name).ok_or_else(|| ArgError::UnknownFlag(token.clone()))?;
+            let value = if flag.takes_value {
+                inline_value.or_else(|| tokens.next()).ok_or(ArgError::MissingRequired(flag.long))?
+            } else if inline_value.is_some() {
+                return Err(ArgError::UnexpectedValue(flag.long));
+            } else {
+                String::new()
+            };
+
+            record_flag(&mut flags, flag, value)?;
+        } else if let Some(short_flags) = token.strip_prefix('-').filter(|s| !s.is_empty()) {
+            for c in short_flags.chars() {
+                let flag = find_short(spec, c).ok_or_else(|| ArgError::UnknownFlag(token.clone()))?;
+                let value = if flag.takes_value {
+                    tokens.next().ok_or(ArgError::MissingRequired(flag.long))?
+                } else {
+                    String::new()
+                };
+
+                record_flag(&mut flags, flag, value)?;
+            }
+        } else {
+            raw_positionals.push(token);
+        }
+    }
+
+    for flag in spec.flags {
+        if flag.arity == Arity::Required && !flags.contains_key(flag.long) {
+            return Err(ArgError::MissingRequired(flag.long));
+        }
+    }
+
+    let positionals = match_positionals(raw_positionals, spec.positionals)?;
+
+    Ok(ParsedArgs { flags, positionals })
+}
+
+// Records one occurrence of `flag`, rejecting a second occurrence of a flag
+// that isn't declared `Repeated`.
+fn record_flag(flags: &mut HashMap<&'static str, Vec<String>>, flag: &FlagSpec, value: String) -> Result<(), ArgError> {
+    let values = flags.entry(flag.long).or_default();
+
+    if !values.is_empty() && flag.arity != Arity::Repeated {
+        return Err(ArgError::TooManyOccurrences(flag.long));
+    }
+
+    values.push(value);
+    Ok(())
+}
+
+// Assigns the positional tokens a command was given to the positional slots
+// it declared, in order. A `Required` slot always claims the next token (or
+// errors if none is left); an `Optional` slot only claims one if there are
+// enough tokens left over to still satisfy every `Required` slot that comes
+// after it, so an optional positional earlier in the spec doesn't steal a
+// token a later required one needed. `Repeated` (only valid as the last
+// slot) claims everything left.
+fn match_positionals(tokens: Vec<String>, spec: &[PositionalSpec]) -> Result<Vec<String>, ArgError> {
+    let mut required_after = vec![0usize; spec.len() + 1];
+    for i in (0..spec.len()).rev() {
+        required_after[i] = required_after[i + 1] + (spec[i].arity == Arity::Required) as usize;
+    }
+
+    let mut out = Vec::new();
+    let mut cursor = 0;
+
+    for (i, positional) in spec.iter().enumerate() {
+        match positional.arity {
+            Arity::Repeated => {
+                out.extend(tokens[cursor..].iter().cloned());
+                cursor = tokens.len();
+            }
+            Arity::Required => {
+                let token = tokens.get(cursor).ok_or(ArgError::MissingRequired(positional.name))?;
+                out.push(token.clone());
+                cursor += 1;
+            }
+            Arity::Optional => {
+                let remaining = tokens.len() - cursor;
+                if remaining > required_after[i + 1] {
+                    out.push(tokens[cursor].clone());
+                    cursor += 1;
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn find_long<'a>(spec: &'a ArgSpec, name: &str) -> Option<&'a FlagSpec> {
+    spec.flags.iter().find(|f| f.long == name)
+}
+
+fn find_short<'a>(spec: &'a ArgSpec, c: char) -> Option<&'a FlagSpec> {
+    spec.flags.iter().find(|f| f.short == Some(c))
+}
+
+// Splits `input` on whitespace, keeping double-quoted spans as a single
+// token (quotes themselves are stripped).
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '"' {
+            chars.next();
+            let token: String = chars.by_ref().take_while(|&c| c != '"').collect();
+            tokens.push(token);
+            continue;
+        }
+
+        let token: String = std::iter::from_fn(|| chars.by_ref().next_if(|c| !c.is_whitespace())).collect();
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUIRED: PositionalSpec = PositionalSpec { name: "required", arity: Arity::Required };
+    const OPTIONAL: PositionalSpec = PositionalSpec { name: "optional", arity: Arity::Optional };
+    const REPEATED: PositionalSpec = PositionalSpec { name: "repeated", arity: Arity::Repeated };
+
+    fn tokens(strs: &[&str]) -> Vec<String> {
+        strs.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn required_positional_claims_its_token() {
+        let out = match_positionals(tokens(&["a"]), &[REQUIRED]).unwrap();
+        assert_eq!(out, tokens(&["a"]));
+    }
+
+    #[test]
+    fn missing_required_positional_errors() {
+        let err = match_positionals(Vec::new(), &[REQUIRED]).unwrap_err();
+        assert_eq!(err, ArgError::MissingRequired("required"));
+    }
+
+    #[test]
+    fn optional_before_required_does_not_starve_required() {
+        // Only one token for an [optional, required] spec: it must go to
+        // the `Required` slot, not be greedily claimed by `Optional`.
+        let out = match_positionals(tokens(&["only"]), &[OPTIONAL, REQUIRED]).unwrap();
+        assert_eq!(out, tokens(&["only"]));
+    }
+
+    #[test]
+    fn optional_before_required_claims_when_there_is_slack() {
+        let out = match_positionals(tokens(&["opt", "req"]), &[OPTIONAL, REQUIRED]).unwrap();
+        assert_eq!(out, tokens(&["opt", "req"]));
+    }
+
+    #[test]
+    fn repeated_slot_collects_everything_left() {
+        let out = match_positionals(tokens(&["a", "b", "c"]), &[REQUIRED, REPEATED]).unwrap();
+        assert_eq!(out, tokens(&["a", "b", "c"]));
+    }
+
+    const VALUE_FLAG: FlagSpec =
+        FlagSpec { long: "channel", short: Some('c'), takes_value: true, arity: Arity::Optional };
+    const SWITCH_FLAG: FlagSpec =
+        FlagSpec { long: "verbose", short: Some('v'), takes_value: false, arity: Arity::Optional };
+    const REQUIRED_FLAG: FlagSpec =
+        FlagSpec { long: "force", short: Some('f'), takes_value: false, arity: Arity::Required };
+    const REPEATED_FLAG: FlagSpec =
+        FlagSpec { long: "tag", short: Some('t'), takes_value: true, arity: Arity::Repeated };
+
+    fn spec(positionals: &'static [PositionalSpec], flags: &'static [FlagSpec]) -> ArgSpec {
+        ArgSpec { positionals, flags }
+    }
+
+    #[test]
+    fn long_flag_with_separate_value() {
+        let s = spec(&[], &[VALUE_FLAG]);
+        let parsed = parse_args("--channel general", &s).unwrap();
+        assert_eq!(parsed.flag("channel"), Some(["general".to_string()].as_slice()));
+    }
+
+    #[test]
+    fn long_flag_with_inline_value() {
+        let s = spec(&[], &[VALUE_FLAG]);
+        let parsed = parse_args("--channel=general", &s).unwrap();
+        assert_eq!(parsed.flag("channel"), Some(["general".to_string()].as_slice()));
+    }
+
+    #[test]
+    fn inline_value_on_non_value_flag_is_rejected() {
+        let s = spec(&[], &[SWITCH_FLAG]);
+        let err = parse_args("--verbose=true", &s).unwrap_err();
+        assert_eq!(err, ArgError::UnexpectedValue("verbose"));
+    }
+
+    #[test]
+    fn bundled_short_flags() {
+        let s = spec(&[], &[SWITCH_FLAG, REQUIRED_FLAG]);
+        let parsed = parse_args("-vf", &s).unwrap();
+        assert!(parsed.flag("verbose").is_some());
+        assert!(parsed.flag("force").is_some());
+    }
+
+    #[test]
+    fn double_dash_stops_flag_parsing() {
+        let s = spec(&[REPEATED], &[SWITCH_FLAG]);
+        let parsed = parse_args("-- --verbose", &s).unwrap();
+        assert_eq!(parsed.positionals(), tokens(&["--verbose"]).as_slice());
+        assert!(parsed.flag("verbose").is_none());
+    }
+
+    #[test]
+    fn repeated_flag_allows_multiple_occurrences() {
+        let s = spec(&[], &[REPEATED_FLAG]);
+        let parsed = parse_args("--tag a --tag b", &s).unwrap();
+        assert_eq!(parsed.flag("tag"), Some(["a".to_string(), "b".to_string()].as_slice()));
+    }
+
+    #[test]
+    fn non_repeated_flag_given_twice_errors() {
+        let s = spec(&[], &[SWITCH_FLAG]);
+        let err = parse_args("--verbose --verbose", &s).unwrap_err();
+        assert_eq!(err, ArgError::TooManyOccurrences("verbose"));
+    }
+
+    #[test]
+    fn missing_required_flag_errors() {
+        let s = spec(&[], &[REQUIRED_FLAG]);
+        let err = parse_args("", &s).unwrap_err();
+        assert_eq!(err, ArgError::MissingRequired("force"));
+    }
+
+    #[test]
+    fn unknown_flag_errors() {
+        let s = spec(&[], &[]);
+        let err = parse_args("--nope", &s).unwrap_err();
+        assert_eq!(err, ArgError::UnknownFlag("--nope".to_string()));
+    }
+
+    #[test]
+    fn quoted_token_is_kept_together() {
+        let s = spec(&[REQUIRED], &[]);
+        let parsed = parse_args("\"two words\"", &s).unwrap();
+        assert_eq!(parsed.positional(0), Some("two words"));
+    }
+}
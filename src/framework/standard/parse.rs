@@ -1,6 +1,11 @@
+use super::{args, stats, usage};
 use super::{Command, CommandGroup, Configuration};
 use crate::client::Context;
 use crate::model::channel::Message;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexSet};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use uwl::{StrExt, StringStream};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -83,6 +88,113 @@ enum ParseMode {
     ByLength,
 }
 
+/// A group's `regex`-enabled commands, compiled once into a [`RegexSet`] so a
+/// candidate message only has to be scanned a single time to find out which
+/// (if any) command it matches.
+struct RegexGroup {
+    set: RegexSet,
+    // Parallel to `set`'s pattern order.
+    commands: Vec<&'static Command>,
+    patterns: Vec<Regex>,
+}
+
+// Regex commands are compiled once per (group, case_insensitive) pair and
+// read from here on the hot dispatch path thereafter. `RwLock` rather than
+// `Mutex` so concurrent messages don't serialise on a single lock just to
+// look a group up. The `case_insensitive` flag is folded into the key
+// because it changes what gets compiled; see `compile_regex_group` for why
+// the key can never go stale.
+static REGEX_GROUPS: Lazy<RwLock<HashMap<(usize, bool), Arc<RegexGroup>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+// Anchors `pattern` at the start of the haystack so it can't match in the
+// middle of an unrelated message, folding in the `(?i)` flag (when asked
+// for) as text rather than only via `RegexBuilder`, so that a `RegexSet`
+// built from `Regex::as_str()` agrees with the individual `Regex`es on case
+// sensitivity.
+fn anchor_pattern(pattern: &str, case_insensitive: bool) -> String {
+    if case_insensitive {
+        format!("(?i)^(?:{})", pattern)
+    } else {
+        format!("^(?:{})", pattern)
+    }
+}
+
+// Compiles the `regex`-enabled commands directly in `group` (not its
+// `sub_groups` - callers that want the whole tree warmed recurse
+// themselves) for `case_insensitive`, caching the result so repeat calls
+// with the same key are a single read-lock lookup. Returns `None` if the
+// group has no `regex`-enabled commands at all, in which case there's
+// nothing to cache.
+//
+// This is called from two places: eagerly by `register_regex_commands`
+// while the framework is being built (so steady-state dispatch never pays
+// compilation cost), and lazily from `CommandParser::regex_command` the
+// first time a group is actually dispatched through. Driving it from both
+// ends like this means forgetting to call `register_regex_commands`, or
+// calling it with a `case_insensitive` value that doesn't match what
+// `Configuration` ends up using, costs a one-time compile on first use
+// instead of silently never matching - the cache key is always exactly the
+// `case_insensitive` the parser is actually running with, because
+// `regex_command` asks for it by `self.config.case_insensitive`.
+fn compile_regex_group(
+    group: &'static CommandGroup,
+    case_insensitive: bool,
+) -> Result<Option<Arc<RegexGroup>>, regex::Error> {
+    let key = (group as *const CommandGroup as usize, case_insensitive);
+
+    if let Some(cached) = REGEX_GROUPS.read().unwrap().get(&key) {
+        return Ok(Some(Arc::clone(cached)));
+    }
+
+    let mut commands = Vec::new();
+    let mut patterns = Vec::new();
+
+    for command in group.commands {
+        let Some(regexes) = command.options.regex else { continue };
+
+        for pattern in regexes {
+            let regex = Regex::new(&anchor_pattern(pattern, case_insensitive))?;
+
+            commands.push(*command);
+            patterns.push(regex);
+        }
+    }
+
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let set = RegexSet::new(patterns.iter().map(Regex::as_str))?;
+    let built = Arc::new(RegexGroup { set, commands, patterns });
+
+    REGEX_GROUPS.write().unwrap().insert(key, Arc::clone(&built));
+
+    Ok(Some(built))
+}
+
+/// Eagerly compiles the `regex`-enabled commands in `group`, and
+/// recursively in its `sub_groups`, for the given `case_insensitive`
+/// setting. Call this once per top-level group while building the
+/// framework, with whichever `case_insensitive` value `Configuration` will
+/// end up using, so a malformed pattern is reported as a setup error and
+/// the first real message doesn't pay compilation latency.
+///
+/// Skipping this call (or calling it with the wrong `case_insensitive`) is
+/// **not** a silent no-match: dispatch still compiles the group itself, the
+/// first time it's needed, under the `case_insensitive` actually in use -
+/// see `compile_regex_group`. This call is purely a warm-up and an early
+/// error check, not a requirement for correctness.
+pub fn register_regex_commands(group: &'static CommandGroup, case_insensitive: bool) -> Result<(), regex::Error> {
+    compile_regex_group(group, case_insensitive)?;
+
+    for sub_group in group.sub_groups {
+        register_regex_commands(sub_group, case_insensitive)?;
+    }
+
+    Ok(())
+}
+
 struct CommandParser<'msg, 'groups, 'config> {
     stream: StringStream<'msg>,
     groups: &'groups [&'static CommandGroup],
@@ -150,6 +262,41 @@ impl<'msg, 'groups, 'config> CommandParser<'msg, 'groups, 'config> {
         None
     }
 
+    // Tries to match the remainder of the stream against the group's
+    // `regex`-enabled commands, in declaration order. Literal-name commands
+    // are always tried first by the caller; this is the fallback path.
+    //
+    // Compiles the group's regexes on demand (cheaply re-using the cache
+    // `register_regex_commands` warms ahead of time, if that was called) so
+    // a bot author who forgot to warm it up still gets working dispatch
+    // instead of silent non-matches.
+    fn regex_command(
+        &mut self,
+        group: &'static CommandGroup,
+    ) -> Result<Option<(&'static Command, Vec<Option<String>>)>, regex::Error> {
+        let Some(regex_group) = compile_regex_group(group, self.config.case_insensitive)? else {
+            return Ok(None);
+        };
+
+        let rest = self.stream.rest();
+        let Some(index) = regex_group.set.matches(rest).into_iter().next() else {
+            return Ok(None);
+        };
+
+        let Some(captures) = regex_group.patterns[index].captures(rest) else {
+            return Ok(None);
+        };
+        let whole = captures.get(0).expect("capture group 0 always matches");
+
+        let args = (1..captures.len())
+            .map(|i| captures.get(i).map(|m| m.as_str().to_owned()))
+            .collect();
+
+        self.stream.increment(whole.end());
+
+        Ok(Some((regex_group.commands[index], args)))
+    }
+
     fn group(&mut self, group: &'static CommandGroup) -> (Option<&'msg str>, &'static CommandGroup) {
         for p in group.options.prefixes {
             let pp = self.next_text(|| p.chars().count());
@@ -176,7 +323,35 @@ impl<'msg, 'groups, 'config> CommandParser<'msg, 'groups, 'config> {
         (None, group)
     }
 
-    fn parse(mut self, prefix: Prefix<'msg>) -> Result<Invoke<'msg>, Option<&'msg str>> {
+    // Builds the final `Invoke::Command`, parsing the remaining stream
+    // against the command's `ArgSpec` (if it declared one) and surfacing any
+    // `ArgError` through the same `Err` channel as an unrecognised command.
+    fn finish(
+        &mut self,
+        prefix: Prefix<'msg>,
+        group: &'static CommandGroup,
+        gprefix: Option<&'msg str>,
+        command: &'static Command,
+        captures: Option<Vec<Option<String>>>,
+    ) -> Result<Invoke<'msg>, ParseError<'msg>> {
+        let args = self.stream.rest();
+
+        let parsed_args = command
+            .options
+            .args
+            .map(|spec| args::parse_args(args, spec))
+            .transpose()
+            .map_err(ParseError::Args)?;
+
+        if let Some(stats) = &self.config.stats {
+            let command_name = command.options.names.first().copied().unwrap_or("");
+            stats.record_invoke(group.name, command_name);
+        }
+
+        Ok(Invoke::Command { prefix, group, gprefix, command, args, captures, parsed_args })
+    }
+
+    fn parse(mut self, prefix: Prefix<'msg>) -> Result<Invoke<'msg>, ParseError<'msg>> {
         let pos = self.stream.offset();
         for group in self.groups {
             let (gprefix, group) = self.group(*group);
@@ -188,43 +363,69 @@ impl<'msg, 'groups, 'config> CommandParser<'msg, 'groups, 'config> {
 
             for command in group.commands {
                 if let Some(command) = self.command(command) {
-                    return Ok(Invoke::Command {
-                        prefix,
-                        group,
-                        gprefix,
-                        command,
-                        args: self.stream.rest(),
-                    });
+                    return self.finish(prefix, group, gprefix, command, None);
                 }
             }
 
+            // Literal names didn't match anything; give the group's
+            // `regex`-enabled commands a shot before moving on.
+            if let Some((command, captures)) = self.regex_command(group).map_err(ParseError::Regex)? {
+                return self.finish(prefix, group, gprefix, command, Some(captures));
+            }
+
             // Only execute the default command if a group prefix is present.
             if let Some(command) = group.options.default_command {
                 if gprefix.is_some() {
-                    return Ok(Invoke::Command {
-                        prefix,
-                        group,
-                        gprefix,
-                        command,
-                        args: self.stream.rest(),
-                    });
+                    return self.finish(prefix, group, gprefix, command, None);
                 }
             }
 
             unsafe { self.stream.set_unchecked(pos) };
         }
 
-        Err(self.unrecognised)
+        // Only a real attempted token is worth recording. `unrecognised` can
+        // be `Some("")` as well as `None` - `command()` still sets it to the
+        // empty text it peeked when there was nothing left to compare
+        // against a name - and counting either as an "unknown command"
+        // would just drown out the tokens users are actually mistyping.
+        if let (Some(stats), Some(token)) = (&self.config.stats, self.unrecognised.filter(|t| !t.is_empty())) {
+            stats.record_unknown(token);
+        }
+
+        Err(ParseError::UnrecognisedCommand(self.unrecognised))
     }
 }
 
+/// Why [`parse_command`] failed to produce an [`Invoke`].
+#[derive(Debug)]
+pub enum ParseError<'a> {
+    /// No group/command matched; carries the last token that was checked
+    /// against a command name, if any.
+    UnrecognisedCommand(Option<&'a str>),
+    /// A command matched, but its arguments didn't satisfy its `ArgSpec`.
+    Args(args::ArgError),
+    /// A matched group's `regex`-enabled commands failed to compile. Only
+    /// reachable if `register_regex_commands` wasn't called up front for
+    /// the `case_insensitive` setting actually in use, deferring the error
+    /// to the first message that hits the group instead of start-up.
+    Regex(regex::Error),
+}
+
+// Breaking change for callers: this used to return `Result<Invoke<'a>,
+// Option<&'a str>>`, and `Invoke::Command`/`Invoke::Help` used to be
+// smaller. Callers now need to match on `ParseError` (which has
+// `UnrecognisedCommand`, `Args`, and `Regex` variants, not just a bare
+// token) and thread through `Invoke::Command`'s new `captures`/`parsed_args`
+// fields and `Invoke::Help`'s new `usage` field. Update call sites (e.g.
+// the dispatcher in this module's parent) in the same change as any of
+// the above.
 pub(crate) fn parse_command<'a>(
     msg: &'a str,
     prefix: Prefix<'a>,
     groups: &[&'static CommandGroup],
     config: &Configuration,
     help_was_set: Option<&[&'static str]>,
-) -> Result<Invoke<'a>, Option<&'a str>> {
+) -> Result<Invoke<'a>, ParseError<'a>> {
     let mut stream = StringStream::new(msg);
     stream.take_while(|s| s.is_whitespace());
 
@@ -236,7 +437,17 @@ pub(crate) fn parse_command<'a>(
 
                 let args = stream.rest();
 
-                return Ok(Invoke::Help { prefix, name, args });
+                // If a specific command was named (`!help ban`), look its
+                // accurate, spec-derived synopsis up instead of making the
+                // bot author keep one written by hand in sync. `usage::lookup`
+                // normalises case and falls back to a leaf-name match, so
+                // this works for both top-level and (unambiguous) nested
+                // commands.
+                let usage = (!args.is_empty())
+                    .then(|| usage::lookup(&usage::usage(groups, config), args, config).cloned())
+                    .flatten();
+
+                return Ok(Invoke::Help { prefix, name, args, usage });
             }
         }
     }
@@ -254,10 +465,62 @@ pub enum Invoke<'a> {
         group: &'static CommandGroup,
         command: &'static Command,
         args: &'a str,
+        // Capture groups from the command's `regex`, in order, if it was
+        // matched via the regex dispatch path rather than by literal name.
+        captures: Option<Vec<Option<String>>>,
+        // `args` parsed against the command's `ArgSpec`, if it declared one.
+        parsed_args: Option<args::ParsedArgs>,
     },
     Help {
         prefix: Prefix<'a>,
         name: &'static str,
         args: &'a str,
+        // The named command's rendered synopsis, from `usage::usage`, if
+        // `args` named one (e.g. `!help ban`) and it was found.
+        usage: Option<String>,
     },
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchor_pattern_is_case_sensitive_by_default() {
+        assert_eq!(anchor_pattern(r"remind me", false), "^(?:remind me)");
+    }
+
+    #[test]
+    fn anchor_pattern_folds_in_case_insensitive_flag() {
+        assert_eq!(anchor_pattern(r"remind me", true), "(?i)^(?:remind me)");
+    }
+
+    #[test]
+    fn anchor_pattern_does_not_match_mid_message() {
+        let regex = Regex::new(&anchor_pattern(r"remind me in (\d+) (minutes|hours)", false)).unwrap();
+        assert!(!regex.is_match("please remind me in 5 minutes"));
+        assert!(regex.is_match("remind me in 5 minutes"));
+    }
+
+    #[test]
+    fn anchored_case_insensitive_pattern_matches_either_case() {
+        let regex = Regex::new(&anchor_pattern(r"remind me in (\d+) (minutes|hours)", true)).unwrap();
+        assert!(regex.is_match("REMIND ME in 5 Hours"));
+    }
+
+    #[test]
+    fn capture_groups_are_extracted_in_order() {
+        let regex = Regex::new(&anchor_pattern(r"remind me in (\d+) (minutes|hours)", false)).unwrap();
+        let captures = regex.captures("remind me in 5 minutes").unwrap();
+
+        let args: Vec<Option<String>> =
+            (1..captures.len()).map(|i| captures.get(i).map(|m| m.as_str().to_owned())).collect();
+
+        assert_eq!(args, vec![Some("5".to_string()), Some("minutes".to_string())]);
+    }
+
+    // `compile_regex_group`'s cache lookup and `CommandParser::regex_command`
+    // need `Command`/`CommandGroup`/`Configuration`, which this module only
+    // ever borrows `&'static` references to and doesn't define, so they are
+    // not exercised by these tests.
+}
@@ -0,0 +1,86 @@
+//! Optional command-invocation metrics, collected at parse time.
+//!
+//! Inspired by atuin's shell-history stats (most-used commands, frequency
+//! counts), this lets a bot author plug a [`CommandStats`] collector into
+//! `Configuration` and get per-command invocation counts - plus insight into
+//! which unrecognised commands users keep mistyping - without instrumenting
+//! every handler by hand.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Receives every resolved command invocation and unrecognised attempt as
+/// [`super::parse::parse_command`] returns.
+pub trait CommandStats: Send + Sync {
+    /// Called right before `parse` returns `Ok` for a resolved command.
+    fn record_invoke(&self, group: &'static str, command: &'static str);
+
+    /// Called right before `parse` returns `Err` because nothing matched,
+    /// with the last non-empty token checked against a command name. Not
+    /// called when no such token was checked.
+    fn record_unknown(&self, attempted: &str);
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CommandCount {
+    count: u64,
+    last_seen_unix_secs: u64,
+}
+
+/// A default, in-memory [`CommandStats`] backed by mutex-guarded maps. Good
+/// enough for a single-process bot wanting a built-in `!stats` command;
+/// swap in your own implementation (e.g. backed by a database) if the
+/// counts need to survive restarts.
+#[derive(Debug, Default)]
+pub struct InMemoryStats {
+    commands: Mutex<HashMap<(&'static str, &'static str), CommandCount>>,
+    unknown: Mutex<HashMap<String, u64>>,
+}
+
+impl InMemoryStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most-invoked `(group, command, count)` triples, sorted by
+    /// descending count.
+    pub fn most_invoked(&self, top_n: usize) -> Vec<(&'static str, &'static str, u64)> {
+        let commands = self.commands.lock().unwrap();
+        let mut counts: Vec<_> =
+            commands.iter().map(|(&(group, command), c)| (group, command, c.count)).collect();
+
+        counts.sort_by(|a, b| b.2.cmp(&a.2));
+        counts.truncate(top_n);
+        counts
+    }
+
+    /// The unrecognised tokens users attempted most often, sorted by
+    /// descending count.
+    pub fn top_unrecognised(&self, top_n: usize) -> Vec<(String, u64)> {
+        let unknown = self.unknown.lock().unwrap();
+        let mut counts: Vec<_> = unknown.iter().map(|(token, &count)| (token.clone(), count)).collect();
+
+        counts.sort_by(|a, b| b.1.cmp(&a.1));
+        counts.truncate(top_n);
+        counts
+    }
+}
+
+impl CommandStats for InMemoryStats {
+    fn record_invoke(&self, group: &'static str, command: &'static str) {
+        let mut commands = self.commands.lock().unwrap();
+        let entry = commands
+            .entry((group, command))
+            .or_insert(CommandCount { count: 0, last_seen_unix_secs: 0 });
+
+        entry.count += 1;
+        entry.last_seen_unix_secs =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    }
+
+    fn record_unknown(&self, attempted: &str) {
+        let mut unknown = self.unknown.lock().unwrap();
+        *unknown.entry(attempted.to_owned()).or_insert(0) += 1;
+    }
+}
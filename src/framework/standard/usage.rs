@@ -0,0 +1,149 @@
+//! Generates human-readable usage synopses from a command tree, so bot
+//! authors don't have to hand-write (and keep in sync with `ArgSpec`) what
+//! `!help` prints for every command.
+//!
+//! Building on the argument-spec machinery in [`super::args`], [`usage`]
+//! walks a slice of top-level [`CommandGroup`]s - recursing through
+//! `sub_groups` and each command's `sub_commands` - and renders a synopsis
+//! like `!group cmd <required> [optional] [--flag VALUE]...` for every
+//! command it finds.
+
+use super::args::{ArgSpec, Arity};
+use super::{Command, CommandGroup, Configuration};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+/// Usage synopses keyed by a command's space-separated path, e.g.
+/// `"mod ban"` for a `ban` command nested under the `mod` group.
+pub type UsageTree = HashMap<String, String>;
+
+/// Walks `groups` and renders a synopsis for every command found, reflecting
+/// `config`'s `case_insensitive` setting and starting each synopsis with the
+/// first of `config.prefixes` (or `!` if none is set); dynamic prefixes and
+/// mentions are not reflected.
+pub fn usage(groups: &[&'static CommandGroup], config: &Configuration) -> UsageTree {
+    let mut tree = UsageTree::new();
+
+    for group in groups {
+        walk_group(group, config, "", &mut tree);
+    }
+
+    tree
+}
+
+fn display_name(name: &str, config: &Configuration) -> String {
+    if config.case_insensitive {
+        name.to_lowercase()
+    } else {
+        name.to_owned()
+    }
+}
+
+fn join_path(parent: &str, segment: &str) -> String {
+    if parent.is_empty() {
+        segment.to_owned()
+    } else {
+        format!("{} {}", parent, segment)
+    }
+}
+
+fn walk_group(group: &'static CommandGroup, config: &Configuration, parent_path: &str, tree: &mut UsageTree) {
+    let path = match group.options.prefixes.first() {
+        Some(prefix) => join_path(parent_path, &display_name(prefix, config)),
+        // Groups with no prefix of their own (e.g. the top-level default
+        // group) don't add a path segment.
+        None => parent_path.to_owned(),
+    };
+
+    for command in group.commands {
+        walk_command(command, config, &path, tree);
+    }
+
+    for sub_group in group.sub_groups {
+        walk_group(sub_group, config, &path, tree);
+    }
+}
+
+fn walk_command(command: &'static Command, config: &Configuration, parent_path: &str, tree: &mut UsageTree) {
+    let Some(name) = command.options.names.first() else { return };
+    let path = join_path(parent_path, &display_name(name, config));
+
+    let bot_prefix = config.prefixes.first().map(String::as_str).unwrap_or("!");
+    let synopsis = format!("{}{}{}", bot_prefix, path, args_synopsis(command.options.args));
+    tree.insert(path.clone(), synopsis);
+
+    for sub in command.options.sub_commands {
+        walk_command(sub, config, &path, tree);
+    }
+}
+
+fn args_synopsis(spec: Option<ArgSpec>) -> String {
+    let Some(spec) = spec else { return String::new() };
+    let mut out = String::new();
+
+    for positional in spec.positionals {
+        match positional.arity {
+            Arity::Required => write!(out, " <{}>", positional.name),
+            Arity::Optional => write!(out, " [{}]", positional.name),
+            Arity::Repeated => write!(out, " [{}]...", positional.name),
+        }
+        .expect("writing to a String never fails");
+    }
+
+    for flag in spec.flags {
+        let value = if flag.takes_value { " VALUE" } else { "" };
+
+        match flag.arity {
+            Arity::Required => write!(out, " --{}{}", flag.long, value),
+            Arity::Optional => write!(out, " [--{}{}]", flag.long, value),
+            Arity::Repeated => write!(out, " [--{}{}]...", flag.long, value),
+        }
+        .expect("writing to a String never fails");
+    }
+
+    out
+}
+
+/// Looks a command up in `tree` by what a user typed after `!help`.
+///
+/// Tried in order:
+/// 1. An exact match against the full path (e.g. `"mod ban"`), normalised
+///    the same way `walk_command` stored it - lowercased when
+///    `config.case_insensitive` is set, so `!help Ban` finds a command
+///    declared (and stored) as `ban`.
+/// 2. If that misses, a match against just the command's leaf name (e.g.
+///    typing `!help ban` for a command nested as `mod ban`), but only if
+///    exactly one entry in the whole tree has that leaf - a leaf name two
+///    different groups both use is ambiguous, so it's left unresolved
+///    rather than guessing which one the user meant.
+pub fn lookup<'a>(tree: &'a UsageTree, query: &str, config: &Configuration) -> Option<&'a String> {
+    let query = query.trim();
+    let normalised = if config.case_insensitive { query.to_lowercase() } else { query.to_owned() };
+
+    if let Some(synopsis) = tree.get(&normalised) {
+        return Some(synopsis);
+    }
+
+    let mut by_leaf = tree.iter().filter(|(path, _)| path.rsplit(' ').next() == Some(normalised.as_str()));
+
+    match (by_leaf.next(), by_leaf.next()) {
+        (Some((_, synopsis)), None) => Some(synopsis),
+        _ => None,
+    }
+}
+
+/// Renders every entry in `tree` as one block, indenting a command's
+/// synopsis by its depth (number of path segments after the first) so nested subcommands
+/// read as a tree under their parent.
+pub fn render(tree: &UsageTree) -> String {
+    let mut paths: Vec<&String> = tree.keys().collect();
+    paths.sort();
+
+    let mut out = String::new();
+    for path in paths {
+        let depth = path.matches(' ').count();
+        let _ = writeln!(out, "{}{}", "  ".repeat(depth), tree[path]);
+    }
+
+    out
+}